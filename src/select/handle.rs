@@ -0,0 +1,203 @@
+use std::cell::RefCell;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+
+use select::signal::{Signal, SyncSignal};
+use select::CaseId;
+
+/// Per-thread state for the selection currently in progress.
+///
+/// Every thread gets exactly one of these, reference-counted so it can be shared outside the
+/// thread that owns it. `promise()` implementations clone the `Arc<Context>` returned by
+/// [`current`] into the channel's wait queue; a `fulfill_send`/`fulfill_recv` running on some
+/// other thread reaches back into that same `Context` to claim the winning case and fire the
+/// waiter's signal. Every field lives behind a `Mutex` for exactly that reason — unlike a plain
+/// thread-local, this type is genuinely accessed cross-thread.
+pub struct Context {
+    inner: Mutex<Inner>,
+}
+
+struct Inner {
+    /// The case some other party picked on our behalf, or `CaseId::none()` until then.
+    selected: CaseId,
+    /// The hook fired to wake us up once `selected` is set. A parked OS thread by default;
+    /// swapped out for an async task's `Waker` by `current_register_signal`.
+    signal: Arc<Signal>,
+}
+
+impl Context {
+    fn new() -> Arc<Context> {
+        Arc::new(Context {
+            inner: Mutex::new(Inner {
+                selected: CaseId::none(),
+                signal: Arc::new(SyncSignal::new()),
+            }),
+        })
+    }
+
+    fn reset(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.selected = CaseId::none();
+        inner.signal = Arc::new(SyncSignal::new());
+    }
+
+    fn register_signal(&self, signal: Arc<Signal>) {
+        self.inner.lock().unwrap().signal = signal;
+    }
+
+    /// Claims `case_id` as the winner, firing the registered signal the first time a case is
+    /// claimed. Returns `false` if this context had already been claimed by an earlier call, so
+    /// only one signal ever fires per fulfilled operation.
+    pub fn try_select(&self, case_id: CaseId) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.selected == CaseId::none() {
+            inner.selected = case_id;
+            inner.signal.fire();
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn selected(&self) -> CaseId {
+        self.inner.lock().unwrap().selected
+    }
+}
+
+thread_local! {
+    static CONTEXT: RefCell<Arc<Context>> = RefCell::new(Context::new());
+}
+
+/// Returns a shareable handle to this thread's selection context.
+///
+/// This is what `promise_send`/`promise_recv` clone into a channel's wait queue so that the
+/// thread fulfilling the operation — not necessarily this one — can reach back into it via
+/// [`Context::try_select`].
+pub fn current() -> Arc<Context> {
+    CONTEXT.with(|c| c.borrow().clone())
+}
+
+/// Clears the previous selection result and reinstalls the default (thread-parking) signal,
+/// ahead of a fresh `Promise` pass.
+pub fn current_reset() {
+    CONTEXT.with(|c| c.borrow().reset());
+}
+
+/// Installs `signal` as this thread's wakeup hook for the selection in progress.
+///
+/// Must be called before the `Promise` pass runs (i.e. right after `current_reset`), since
+/// `promise_send`/`promise_recv` capture whatever signal is current *at promise time* into the
+/// wait queue — registering it afterwards would leave the wait queue pointing at the signal
+/// `current_reset` installed instead.
+pub fn current_register_signal(signal: Arc<Signal>) {
+    CONTEXT.with(|c| c.borrow().register_signal(signal));
+}
+
+/// Claims `case_id` as the winner of the selection in progress, firing this thread's signal
+/// the first time a case is claimed.
+///
+/// This acts on the calling thread's own context, so it is what the waiting thread itself
+/// uses (e.g. `Machine` claiming `CaseId::abort()` when a case turns out to be ready while
+/// promising). A party on another thread, such as a channel's `fulfill_send`/`fulfill_recv`,
+/// instead calls [`Context::try_select`] on the `Arc<Context>` obtained through [`current`].
+pub fn current_try_select(case_id: CaseId) -> bool {
+    CONTEXT.with(|c| c.borrow().try_select(case_id))
+}
+
+/// Returns the case this thread ended up being selected for, or `CaseId::none()`.
+pub fn current_selected() -> CaseId {
+    CONTEXT.with(|c| c.borrow().selected())
+}
+
+/// Parks the current thread until its registered signal fires or `deadline` passes.
+///
+/// Only called from the blocking path; when a `Waker` has been registered instead (via
+/// `current_register_signal`), `Machine::transition` returns control to the caller as
+/// `State::Pending` rather than calling this.
+pub fn current_wait_until(deadline: Option<Instant>) {
+    loop {
+        if current_selected() != CaseId::none() {
+            return;
+        }
+
+        match deadline {
+            None => thread::park(),
+            Some(end) => {
+                let now = Instant::now();
+                if now >= end {
+                    return;
+                }
+                thread::park_timeout(end - now);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::task::{RawWaker, RawWakerVTable, Waker};
+    use std::thread;
+
+    use select::signal::AsyncSignal;
+    use select::CaseId;
+
+    use super::{current, current_register_signal, current_reset, current_selected};
+
+    fn flag_waker(flag: Arc<AtomicBool>) -> Waker {
+        fn vtable() -> &'static RawWakerVTable {
+            static VTABLE: RawWakerVTable =
+                RawWakerVTable::new(clone, wake, wake_by_ref, drop);
+
+            unsafe fn clone(data: *const ()) -> RawWaker {
+                let flag = Arc::from_raw(data as *const AtomicBool);
+                let cloned = flag.clone();
+                ::std::mem::forget(flag);
+                RawWaker::new(Arc::into_raw(cloned) as *const (), vtable())
+            }
+            // Consumes the reference the `RawWaker` held, same as `Arc::from_raw` dropping it.
+            unsafe fn wake(data: *const ()) {
+                let flag = Arc::from_raw(data as *const AtomicBool);
+                flag.store(true, Ordering::SeqCst);
+            }
+            // Must NOT consume the reference — the caller keeps using the `Waker` afterwards.
+            unsafe fn wake_by_ref(data: *const ()) {
+                let flag = Arc::from_raw(data as *const AtomicBool);
+                flag.store(true, Ordering::SeqCst);
+                ::std::mem::forget(flag);
+            }
+            unsafe fn drop(data: *const ()) {
+                Arc::from_raw(data as *const AtomicBool);
+            }
+
+            &VTABLE
+        }
+
+        let raw = RawWaker::new(Arc::into_raw(flag) as *const (), vtable());
+        unsafe { Waker::from_raw(raw) }
+    }
+
+    #[test]
+    fn foreign_thread_reaching_context_wakes_the_async_signal() {
+        current_reset();
+
+        let woken = Arc::new(AtomicBool::new(false));
+        current_register_signal(Arc::new(AsyncSignal::new(flag_waker(woken.clone()))));
+
+        assert_eq!(current_selected(), CaseId::none());
+
+        // Simulate a `fulfill_send`/`fulfill_recv` running on another thread: it only ever
+        // has the `Arc<Context>` a wait queue handed it, never this thread's thread-local.
+        let ctx = current();
+        thread::spawn(move || {
+            assert!(ctx.try_select(CaseId::abort()));
+        })
+        .join()
+        .unwrap();
+
+        assert!(woken.load(Ordering::SeqCst));
+        assert_eq!(current_selected(), CaseId::abort());
+    }
+}
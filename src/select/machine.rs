@@ -1,8 +1,11 @@
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
 use std::time::Instant;
 
 use {Receiver, Sender};
 use err::{TryRecvError, TrySendError};
 use select::handle;
+use select::signal::AsyncSignal;
 use select::CaseId;
 use utils;
 
@@ -18,6 +21,7 @@ enum State {
     Count,
     Try { disconnected_count: usize },
     Promise { disconnected_count: usize },
+    Pending,
     Revoke { case_id: CaseId },
     Fulfill { case_id: CaseId },
     Disconnected,
@@ -36,12 +40,27 @@ enum State {
 //     }
 // }
 
+/// A type-erased channel case.
+///
+/// `Machine::case` drives implementors through the same `Try`/`Promise`/`Revoke` progression
+/// `send`/`recv` drive `Sender<T>`/`Receiver<T>` through, just without needing to know the
+/// message type. This is what lets `select::Select` hold a runtime `Vec` of heterogeneous
+/// cases instead of a fixed, macro-unrolled sequence of `send`/`recv` calls.
+pub trait Case {
+    fn case_id(&self) -> CaseId;
+    fn is_disconnected(&self) -> bool;
+    fn can_proceed(&self) -> bool;
+    fn promise(&self);
+    fn revoke(&self);
+}
+
 pub struct Machine {
     state: State,
     index: usize,
     start_index: usize,
     first_id: CaseId,
     deadline: Option<Instant>,
+    waker: Option<Waker>,
 
     len: usize,
     send_case_count: usize,
@@ -49,6 +68,7 @@ pub struct Machine {
     has_disconnected_case: bool,
     has_would_block_case: bool,
     has_timed_out_case: bool,
+    biased: bool,
 }
 
 impl Machine {
@@ -65,6 +85,7 @@ impl Machine {
             start_index: 0,
             first_id: CaseId::none(),
             deadline,
+            waker: None,
 
             len: 0,
             send_case_count: 0,
@@ -72,9 +93,23 @@ impl Machine {
             has_disconnected_case: false,
             has_would_block_case: false,
             has_timed_out_case: false,
+            biased: false,
         }
     }
 
+    /// Makes this `Machine` favor earlier-registered cases.
+    ///
+    /// Normally `step` starts each `Try`/`Promise` pass at a random case so that, under
+    /// contention, every case gets a fair shot. With bias enabled, passes always start at
+    /// case `0` and scan in registration order, so the earliest-declared case always wins
+    /// when more than one is simultaneously ready. `all_disconnected`, `would_block` and
+    /// `timed_out` precedence are unaffected; only the starting offset changes.
+    #[inline]
+    pub fn with_bias(mut self, biased: bool) -> Self {
+        self.biased = biased;
+        self
+    }
+
     #[inline(always)]
     pub fn send<T>(&mut self, tx: &Sender<T>, mut msg: T) -> Result<(), T> {
         if !self.step(tx.case_id()) {
@@ -118,7 +153,11 @@ impl Machine {
                     }
                 }
             },
-            State::Count | State::Disconnected | State::WouldBlock | State::TimedOut => {}
+            State::Count
+            | State::Pending
+            | State::Disconnected
+            | State::WouldBlock
+            | State::TimedOut => {}
             State::Dead => panic!("cannot use the same `Select` for multiple selections")
         }
         Err(msg)
@@ -166,12 +205,71 @@ impl Machine {
                     }
                 }
             },
-            State::Count | State::Disconnected | State::WouldBlock | State::TimedOut => {}
+            State::Count
+            | State::Pending
+            | State::Disconnected
+            | State::WouldBlock
+            | State::TimedOut => {}
             State::Dead => panic!("cannot use the same `Select` for multiple selections")
         }
         Err(())
     }
 
+    /// Drives a single type-erased case one step further.
+    ///
+    /// Returns `true` once `case` is the one that gets to proceed: it was already ready
+    /// during `Try`, or it's the case `Fulfill` identifies as the winner. Unlike `send`/
+    /// `recv`, nothing is actually transferred here — `Select` calls this purely to find out
+    /// which case won, and leaves completing the operation to the caller. As with `send`/
+    /// `recv`, callers are expected to call this for every case, every round, exactly as the
+    /// `select!` macro does for its fixed set of arms.
+    #[inline(always)]
+    pub fn case(&mut self, case: &Case) -> bool {
+        if !self.step(case.case_id()) {
+            return false;
+        }
+
+        match self.state {
+            State::Try {
+                ref mut disconnected_count,
+            } => {
+                if case.is_disconnected() && !case.can_proceed() {
+                    *disconnected_count += 1;
+                } else if case.can_proceed() {
+                    return true;
+                }
+            }
+            State::Promise {
+                ref mut disconnected_count,
+            } => {
+                case.promise();
+
+                if case.is_disconnected() && !case.can_proceed() {
+                    *disconnected_count += 1;
+                } else if case.can_proceed() {
+                    handle::current_try_select(CaseId::abort());
+                }
+            }
+            State::Revoke { case_id } => {
+                if case.case_id() != case_id {
+                    case.revoke();
+                }
+            },
+            State::Fulfill { case_id } => {
+                if case.case_id() == case_id {
+                    return true;
+                }
+            },
+            State::Count
+            | State::Pending
+            | State::Disconnected
+            | State::WouldBlock
+            | State::TimedOut => {}
+            State::Dead => panic!("cannot use the same `Select` for multiple selections")
+        }
+        false
+    }
+
     #[inline]
     pub fn disconnected(&mut self) -> bool {
         if !self.step(CaseId::disconnected()) {
@@ -196,6 +294,21 @@ impl Machine {
         self.state == State::TimedOut
     }
 
+    /// Returns `true` if a case just won via the `Fulfill` phase rather than the `Try` phase.
+    ///
+    /// `Select::run` uses this to tell the two kinds of win `case` can report apart: a `Try`
+    /// win means the case was never promised, so there's nothing to revoke; a `Fulfill` win
+    /// means `Revoke` deliberately skipped revoking the winner so it could be fulfilled here,
+    /// which leaves a dangling promise `Select` — unlike `send`/`recv`, which fulfill inline —
+    /// must clean up itself before handing the token back to the caller.
+    #[inline]
+    pub fn in_fulfill(&self) -> bool {
+        match self.state {
+            State::Fulfill { .. } => true,
+            _ => false,
+        }
+    }
+
     #[inline(always)]
     pub fn step(&mut self, case_id: CaseId) -> bool {
         assert!(
@@ -225,7 +338,11 @@ impl Machine {
                 disconnected_count: 0,
             };
             self.index = 0;
-            self.start_index = utils::small_random(self.len);
+            self.start_index = if self.biased {
+                0
+            } else {
+                utils::small_random(self.len)
+            };
         }
 
         if self.index >= 2 * self.len {
@@ -238,6 +355,30 @@ impl Machine {
         self.start_index <= i && i < self.start_index + self.len
     }
 
+    /// Drives the state machine from an async context instead of blocking the calling thread.
+    ///
+    /// Async select wrappers call this before each pass over their cases in place of relying
+    /// on `transition`'s `current_wait_until` path. The first time a case would have to wait,
+    /// `transition` installs the task's `Waker` as a `Signal` on the channel's wait queue and
+    /// leaves the machine in `State::Pending`, at which point this returns `Poll::Pending`.
+    /// Once that waker fires, the executor polls again; this call then drives `transition`
+    /// itself to resume the machine at `State::Revoke`, exactly as if a parked thread had
+    /// woken up from `current_wait_until`, before handing control back to the caller.
+    #[inline]
+    pub fn poll(&mut self, cx: &mut Context) -> Poll<()> {
+        self.waker = Some(cx.waker().clone());
+
+        if self.state == State::Pending {
+            self.transition();
+        }
+
+        if self.state == State::Pending {
+            Poll::Pending
+        } else {
+            Poll::Ready(())
+        }
+    }
+
     #[inline(always)]
     fn transition(&mut self) {
         match self.state {
@@ -251,6 +392,16 @@ impl Machine {
                     self.state = State::WouldBlock;
                 } else {
                     handle::current_reset();
+
+                    // Must happen before the `Promise` pass below runs: `promise_send`/
+                    // `promise_recv` capture whatever signal is current *at promise time* into
+                    // the wait queue, so the async signal has to already be in place, not
+                    // swapped in afterwards once every case has promised against the stale one
+                    // `current_reset` installed.
+                    if let Some(waker) = self.waker.clone() {
+                        handle::current_register_signal(Arc::new(AsyncSignal::new(waker)));
+                    }
+
                     self.state = State::Promise { disconnected_count: 0 };
                 }
             }
@@ -260,6 +411,9 @@ impl Machine {
 
                 if self.has_disconnected_case && all_disconnected {
                     handle::current_try_select(CaseId::abort());
+                } else if self.waker.is_some() {
+                    self.state = State::Pending;
+                    return;
                 } else {
                     handle::current_wait_until(self.deadline);
                 }
@@ -267,6 +421,15 @@ impl Machine {
                     case_id: handle::current_selected(),
                 };
             }
+            State::Pending => {
+                // Only resume once the registered signal has actually fired and claimed a
+                // case on our behalf; otherwise a spurious re-poll would revoke every promised
+                // case and restart from `Try` for nothing.
+                let selected = handle::current_selected();
+                if selected != CaseId::none() {
+                    self.state = State::Revoke { case_id: selected };
+                }
+            }
             State::Revoke { case_id } => {
                 self.state = State::Fulfill { case_id };
             }
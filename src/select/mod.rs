@@ -0,0 +1,6 @@
+mod handle;
+mod machine;
+mod signal;
+mod select;
+
+pub use self::select::{Iter, Select, TryIter};
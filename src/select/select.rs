@@ -0,0 +1,409 @@
+use std::any::Any;
+use std::time::{Duration, Instant};
+
+use {Receiver, Sender};
+use select::machine::{Case, Machine};
+use select::CaseId;
+
+/// A type-erased channel end `Select` can drive through `Machine::case`, plus the bit of
+/// extra plumbing `iter`/`try_iter` need to know whether there's a message to receive at all.
+trait SelectHandle: Case + Send {
+    fn is_send(&self) -> bool;
+
+    /// Receives a message and boxes it as `Any`, for `Select::iter`/`try_iter`.
+    ///
+    /// Only ever called on a receiver case: `iter`/`try_iter` filter out senders up front,
+    /// since there is nothing to receive from them.
+    fn recv_any(&self) -> Result<Box<Any + Send>, ()>;
+}
+
+impl<T: 'static + Send> Case for Sender<T> {
+    fn case_id(&self) -> CaseId {
+        Sender::case_id(self)
+    }
+
+    fn is_disconnected(&self) -> bool {
+        Sender::is_disconnected(self)
+    }
+
+    fn can_proceed(&self) -> bool {
+        Sender::can_send(self)
+    }
+
+    fn promise(&self) {
+        Sender::promise_send(self)
+    }
+
+    fn revoke(&self) {
+        Sender::revoke_send(self)
+    }
+}
+
+impl<T: 'static + Send> SelectHandle for Sender<T> {
+    fn is_send(&self) -> bool {
+        true
+    }
+
+    fn recv_any(&self) -> Result<Box<Any + Send>, ()> {
+        unreachable!("a `Sender` case is never asked to receive a message")
+    }
+}
+
+impl<T: 'static + Send> Case for Receiver<T> {
+    fn case_id(&self) -> CaseId {
+        Receiver::case_id(self)
+    }
+
+    fn is_disconnected(&self) -> bool {
+        Receiver::is_disconnected(self)
+    }
+
+    fn can_proceed(&self) -> bool {
+        Receiver::can_recv(self)
+    }
+
+    fn promise(&self) {
+        Receiver::promise_recv(self)
+    }
+
+    fn revoke(&self) {
+        Receiver::revoke_recv(self)
+    }
+}
+
+impl<T: 'static + Send> SelectHandle for Receiver<T> {
+    fn is_send(&self) -> bool {
+        false
+    }
+
+    fn recv_any(&self) -> Result<Box<Any + Send>, ()> {
+        self.try_recv().map(|msg| Box::new(msg) as Box<Any + Send>).map_err(|_| ())
+    }
+}
+
+/// A runtime-dynamic, heterogeneous select set.
+///
+/// Unlike the `select!` macro, whose case set is fixed at macro-expansion time, `Select`
+/// stores its cases in a `Vec` and can grow or shrink between selections. This mirrors the
+/// old `std::sync::mpsc::Select`/`Handle` API: `recv`/`send` register a case and return a
+/// stable token, `remove` drops one, and `ready`/`select`/`select_timeout` tell you which
+/// token is ready to proceed. Internally every one of those drives a fresh `Machine` over the
+/// registered cases, the same `Count`→`Try`→`Promise`→`Revoke`→`Fulfill` progression the
+/// `select!` macro drives over its fixed arms, so the precedence of `all_disconnected`,
+/// `would_block` and `timed_out` can't drift between the two call sites.
+///
+/// The caller completes the operation itself once a token comes back, e.g.:
+///
+/// ```ignore
+/// let mut sel = Select::new();
+/// let a = sel.recv(&rx_a);
+/// let b = sel.recv(&rx_b);
+///
+/// match sel.select() {
+///     token if token == a => println!("{:?}", rx_a.recv()),
+///     token if token == b => println!("{:?}", rx_b.recv()),
+///     _ => unreachable!(),
+/// }
+/// ```
+pub struct Select {
+    next_token: usize,
+    cases: Vec<(usize, Box<SelectHandle>)>,
+    biased: bool,
+}
+
+impl Select {
+    /// Creates an empty dynamic select set.
+    #[inline]
+    pub fn new() -> Select {
+        Select {
+            next_token: 0,
+            cases: Vec::new(),
+            biased: false,
+        }
+    }
+
+    /// Makes this `Select` favor earlier-registered cases.
+    ///
+    /// Normally `ready`/`select`/`select_timeout` start each pass at a random case. With bias
+    /// enabled, passes always start at the first registered case and scan in registration
+    /// order, so the earliest-registered case always wins when more than one is simultaneously
+    /// ready — a cheap priority scheme for e.g. a control channel over a bulk-data one.
+    #[inline]
+    pub fn with_bias(mut self, biased: bool) -> Select {
+        self.biased = biased;
+        self
+    }
+
+    /// Registers `rx` as a case and returns its token.
+    pub fn recv<T: 'static + Send>(&mut self, rx: &Receiver<T>) -> usize {
+        self.insert(Box::new(rx.clone()))
+    }
+
+    /// Registers `tx` as a case and returns its token.
+    pub fn send<T: 'static + Send>(&mut self, tx: &Sender<T>) -> usize {
+        self.insert(Box::new(tx.clone()))
+    }
+
+    /// Drops the case identified by `token`.
+    ///
+    /// Does nothing if `token` is not currently registered (e.g. it was already removed).
+    pub fn remove(&mut self, token: usize) {
+        self.cases.retain(|&(t, _)| t != token);
+    }
+
+    fn insert(&mut self, handle: Box<SelectHandle>) -> usize {
+        let token = self.next_token;
+        self.next_token += 1;
+        self.cases.push((token, handle));
+        token
+    }
+
+    fn new_machine(&self, deadline: Option<Instant>) -> Machine {
+        Machine::with_deadline(deadline).with_bias(self.biased)
+    }
+
+    /// Drives `machine` over every case matching `include` until one of them proceeds, or
+    /// until `machine` reaches a terminal state (`all_disconnected`, `would_block`, or the
+    /// deadline running out). `stop` is called once per round to check for that terminal
+    /// state, since `ready` and `select`/`select_timeout` disagree on what counts as one.
+    fn run<F, S>(&self, mut machine: Machine, include: F, mut stop: S) -> Option<usize>
+    where
+        F: Fn(&SelectHandle) -> bool,
+        S: FnMut(&mut Machine) -> bool,
+    {
+        let cases: Vec<&(usize, Box<SelectHandle>)> =
+            self.cases.iter().filter(|&&(_, ref h)| include(h.as_ref())).collect();
+
+        if cases.is_empty() {
+            return None;
+        }
+
+        loop {
+            for &&(token, ref case) in &cases {
+                if machine.case(case.as_ref()) {
+                    // A `Try`-phase win means `case` was never promised, so there's nothing to
+                    // revoke. A `Fulfill`-phase win means `Revoke` deliberately left it
+                    // un-revoked so it could be fulfilled — since `Select` doesn't fulfill it
+                    // inline the way `send`/`recv` do, it has to revoke that dangling promise
+                    // itself before handing the token back to the caller.
+                    if machine.in_fulfill() {
+                        case.revoke();
+                    }
+                    return Some(token);
+                }
+            }
+
+            if stop(&mut machine) {
+                return None;
+            }
+        }
+    }
+
+    /// Returns the token of a case that can proceed right now, without blocking and without
+    /// transferring a message.
+    ///
+    /// Returns `None` if no case is currently ready.
+    pub fn ready(&self) -> Option<usize> {
+        let machine = self.new_machine(None);
+        self.run(
+            machine,
+            |_| true,
+            |m| m.disconnected() || m.would_block(),
+        )
+    }
+
+    /// Blocks until some registered case can proceed, then returns its token.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `Select` has no registered cases, or if every registered case is
+    /// disconnected, since no case can then ever proceed.
+    pub fn select(&mut self) -> usize {
+        self.wait(None, |_| true)
+            .expect("`Select::select` called with no case that can proceed")
+    }
+
+    /// Like [`select`](Select::select), but gives up and returns `None` once `timeout` elapses.
+    ///
+    /// Also returns `None`, instead of panicking, when there are no registered cases or every
+    /// registered case is disconnected.
+    pub fn select_timeout(&mut self, timeout: Duration) -> Option<usize> {
+        self.wait(Some(Instant::now() + timeout), |_| true)
+    }
+
+    fn wait<F>(&mut self, deadline: Option<Instant>, include: F) -> Option<usize>
+    where
+        F: Fn(&SelectHandle) -> bool,
+    {
+        let machine = self.new_machine(deadline);
+        self.run(machine, include, |m| m.disconnected() || m.timed_out())
+    }
+
+    /// Blockingly drains every registered receiver in fair order.
+    ///
+    /// Each call to `next` runs the same `Try`/`Promise`/`Revoke` cycle as `select`, then
+    /// receives from whichever case fired. The iterator ends once every registered receiver
+    /// is disconnected, the same `all_disconnected` condition `transition` uses to stop
+    /// blocking. Registered senders never fire here: `iter` only drives the receiver cases.
+    #[inline]
+    pub fn iter(&mut self) -> Iter {
+        Iter { select: self }
+    }
+
+    /// Like [`iter`](Select::iter), but never blocks: it yields only the receivers that are
+    /// ready right now, via the same `would_block` path `ready` uses, and stops as soon as
+    /// none are.
+    #[inline]
+    pub fn try_iter(&mut self) -> TryIter {
+        TryIter { select: self }
+    }
+
+    fn recv_ready(&mut self, token: usize) -> Option<Box<Any + Send>> {
+        let pos = self.cases.iter().position(|&(t, _)| t == token)?;
+        self.cases[pos].1.recv_any().ok()
+    }
+}
+
+impl Default for Select {
+    #[inline]
+    fn default() -> Self {
+        Select::new()
+    }
+}
+
+/// A blocking iterator over the receiver cases of a [`Select`], created by [`Select::iter`].
+pub struct Iter<'a> {
+    select: &'a mut Select,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (usize, Box<Any + Send>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let token = self.select.wait(None, |h| !h.is_send())?;
+        self.select.recv_ready(token).map(|msg| (token, msg))
+    }
+}
+
+/// A non-blocking iterator over the receiver cases of a [`Select`], created by
+/// [`Select::try_iter`].
+pub struct TryIter<'a> {
+    select: &'a mut Select,
+}
+
+impl<'a> Iterator for TryIter<'a> {
+    type Item = (usize, Box<Any + Send>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let machine = self.select.new_machine(None);
+        let token = self.select.run(
+            machine,
+            |h| !h.is_send(),
+            |m| m.disconnected() || m.would_block(),
+        )?;
+        self.select.recv_ready(token).map(|msg| (token, msg))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use unbounded;
+
+    use super::Select;
+
+    #[test]
+    fn select_picks_the_ready_case() {
+        let (tx, rx) = unbounded();
+        tx.send(1).unwrap();
+
+        let mut sel = Select::new();
+        let token = sel.recv(&rx);
+
+        assert_eq!(sel.select(), token);
+        assert_eq!(rx.recv(), Ok(1));
+    }
+
+    #[test]
+    fn remove_drops_a_case() {
+        let (_tx_a, rx_a) = unbounded::<i32>();
+        let (tx_b, rx_b) = unbounded::<i32>();
+        tx_b.send(2).unwrap();
+
+        let mut sel = Select::new();
+        let a = sel.recv(&rx_a);
+        let b = sel.recv(&rx_b);
+        sel.remove(a);
+
+        assert_eq!(sel.select(), b);
+    }
+
+    #[test]
+    fn ready_returns_none_when_nothing_is_ready() {
+        let (_tx, rx) = unbounded::<i32>();
+
+        let mut sel = Select::new();
+        sel.recv(&rx);
+
+        assert_eq!(sel.ready(), None);
+    }
+
+    #[test]
+    fn biased_prefers_the_earlier_registered_case() {
+        let (tx_a, rx_a) = unbounded();
+        let (tx_b, rx_b) = unbounded();
+        tx_a.send(1).unwrap();
+        tx_b.send(2).unwrap();
+
+        let mut sel = Select::new().with_bias(true);
+        let a = sel.recv(&rx_a);
+        let _b = sel.recv(&rx_b);
+
+        assert_eq!(sel.select(), a);
+    }
+
+    #[test]
+    fn iter_skips_senders_and_stops_on_disconnect() {
+        let (tx, rx) = unbounded();
+        tx.send(1).unwrap();
+        tx.send(2).unwrap();
+        drop(tx);
+
+        // A registered, permanently-ready sender case (unbounded, live receiver on the other
+        // end) must never be what `iter` yields, and must never stop it from noticing `rx`
+        // is the only receiver and it's disconnected.
+        let (side_tx, _side_rx) = unbounded::<i32>();
+
+        let mut sel = Select::new();
+        sel.send(&side_tx);
+        sel.recv(&rx);
+
+        let received: Vec<i32> = sel
+            .iter()
+            .map(|(_, msg)| *msg.downcast::<i32>().unwrap())
+            .collect();
+
+        assert_eq!(received, vec![1, 2]);
+    }
+
+    #[test]
+    fn try_iter_stops_once_nothing_is_ready() {
+        let (tx, rx) = unbounded();
+        tx.send(1).unwrap();
+
+        // Same trap as `iter_skips_senders_and_stops_on_disconnect`: an always-ready sender
+        // case must not be what `try_iter` returns, and must not keep it from stopping once
+        // `rx` has nothing left.
+        let (side_tx, _side_rx) = unbounded::<i32>();
+
+        let mut sel = Select::new();
+        sel.send(&side_tx);
+        sel.recv(&rx);
+
+        let received: Vec<i32> = sel
+            .try_iter()
+            .map(|(_, msg)| *msg.downcast::<i32>().unwrap())
+            .collect();
+
+        assert_eq!(received, vec![1]);
+    }
+}
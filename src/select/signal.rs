@@ -0,0 +1,126 @@
+use std::task::Waker;
+use std::thread::{self, Thread};
+
+/// A wakeup hook installed into a channel's wait queue by whichever side is waiting on an
+/// operation.
+///
+/// One `Signal` is held per selection in the waiting thread's `handle::Context` (installed
+/// just before the `Promise` pass, see `Machine::transition`'s `State::Try` arm) and is shared
+/// by every case promised in that pass. `Context::try_select` calls `fire()` on it the first
+/// time a case is claimed, so it fires once per fulfilled operation. This is the indirection that lets the very same
+/// `Machine` be driven by a blocked OS thread or by a polled `Future`, without either side
+/// knowing which one it is.
+pub trait Signal: Send + Sync {
+    /// Wakes up whoever installed this signal.
+    fn fire(&self) -> bool;
+
+    /// A stable address identifying this signal, used to dedup hooks that belong to the same
+    /// waiter (e.g. the same thread or task registered on more than one case).
+    fn as_ptr(&self) -> *const ();
+}
+
+/// Wakes a parked OS thread.
+///
+/// This is the `Signal` installed by the ordinary blocking `select!`/`recv`/`send` path.
+pub struct SyncSignal(Thread);
+
+impl SyncSignal {
+    #[inline]
+    pub fn new() -> Self {
+        SyncSignal(thread::current())
+    }
+}
+
+impl Default for SyncSignal {
+    #[inline]
+    fn default() -> Self {
+        SyncSignal::new()
+    }
+}
+
+impl Signal for SyncSignal {
+    #[inline]
+    fn fire(&self) -> bool {
+        self.0.unpark();
+        true
+    }
+
+    #[inline]
+    fn as_ptr(&self) -> *const () {
+        self as *const SyncSignal as *const ()
+    }
+}
+
+/// Wakes a polled `Future` via its `Waker`.
+///
+/// Installed instead of `SyncSignal` when a `Machine` is driven through `Machine::poll`
+/// rather than through the blocking `current_wait_until` path.
+pub struct AsyncSignal(Waker);
+
+impl AsyncSignal {
+    #[inline]
+    pub fn new(waker: Waker) -> Self {
+        AsyncSignal(waker)
+    }
+}
+
+impl Signal for AsyncSignal {
+    #[inline]
+    fn fire(&self) -> bool {
+        self.0.wake_by_ref();
+        true
+    }
+
+    #[inline]
+    fn as_ptr(&self) -> *const () {
+        self as *const AsyncSignal as *const ()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::task::{RawWaker, RawWakerVTable, Waker};
+
+    use super::{AsyncSignal, Signal, SyncSignal};
+
+    #[test]
+    fn sync_signal_fires() {
+        let signal = SyncSignal::new();
+        assert!(signal.fire());
+    }
+
+    #[test]
+    fn as_ptr_identifies_a_signal_across_clones_but_not_across_construction() {
+        use std::sync::Arc;
+
+        let a: Arc<Signal> = Arc::new(SyncSignal::new());
+        let b = Arc::clone(&a);
+        let c: Arc<Signal> = Arc::new(SyncSignal::new());
+
+        // Cloning an `Arc<Signal>` (e.g. to register the same waiter on more than one case)
+        // must not look like a second, distinct waiter to whatever dedups by `as_ptr`.
+        assert_eq!(a.as_ptr(), b.as_ptr());
+        // Two independently constructed signals must never collide.
+        assert_ne!(a.as_ptr(), c.as_ptr());
+    }
+
+    fn noop_waker() -> Waker {
+        fn clone(_: *const ()) -> RawWaker {
+            RawWaker::new(ptr(), &VTABLE)
+        }
+        fn noop(_: *const ()) {}
+        fn ptr() -> *const () {
+            static DATA: () = ();
+            &DATA as *const ()
+        }
+
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+        unsafe { Waker::from_raw(RawWaker::new(ptr(), &VTABLE)) }
+    }
+
+    #[test]
+    fn async_signal_fires() {
+        let signal = AsyncSignal::new(noop_waker());
+        assert!(signal.fire());
+    }
+}